@@ -1,10 +1,12 @@
 mod units;
 
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 use serde::{Deserialize, Serialize};
 use std::ops::Range;
 use units::{Meters, Yards};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 struct FSXChallenge {
     name: String,
@@ -13,7 +15,7 @@ struct FSXChallenge {
     stations: Vec<Station>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 struct Station {
     array_index: usize,
@@ -39,35 +41,205 @@ struct Station {
     obstacle_dist: Meters,
 }
 
-fn yards_within(bounds: Range<Yards>, min_gap: Yards) -> RandYardsIter {
-    RandYardsIter {
-        rng: ::rand::thread_rng(),
-        bounds,
-        min_gap,
-        last: None,
+/// Number of candidates drawn per accepted sample, before scaling by how many
+/// samples have already been placed (Mitchell's best-candidate method).
+const BEST_CANDIDATE_BASE_K: usize = 4;
+/// Upper bound on candidates considered for a single sample before giving up
+/// and reporting the range/min_gap combination as infeasible.
+const MAX_CANDIDATE_ATTEMPTS: usize = 10_000;
+
+#[derive(Debug)]
+enum SpacingError {
+    InfeasibleRange {
+        min_gap: Yards,
+        count: usize,
+        range: Yards,
+    },
+    Exhausted {
+        min_gap: Yards,
+        count: usize,
+    },
+    TargetAvgUnreachable {
+        target_avg: Yards,
+        tolerance: Yards,
+    },
+    InvalidRange {
+        min: Yards,
+        max: Yards,
+        min_gap: Yards,
+    },
+}
+
+impl std::fmt::Display for SpacingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SpacingError::InfeasibleRange {
+                min_gap,
+                count,
+                range,
+            } => write!(
+                f,
+                "cannot place {count} stations at least {:.0} yards apart within a {:.0} yard range",
+                min_gap.as_float(),
+                range.as_float()
+            ),
+            SpacingError::Exhausted { min_gap, count } => write!(
+                f,
+                "gave up after {MAX_CANDIDATE_ATTEMPTS} attempts trying to place {count} stations \
+at least {:.0} yards apart",
+                min_gap.as_float()
+            ),
+            SpacingError::TargetAvgUnreachable {
+                target_avg,
+                tolerance,
+            } => write!(
+                f,
+                "gave up after {MAX_TARGET_AVG_ATTEMPTS} attempts trying to reach an average \
+target distance of {:.0} yards (+/- {:.0})",
+                target_avg.as_float(),
+                tolerance.as_float()
+            ),
+            SpacingError::InvalidRange { min, max, min_gap } => {
+                if min_gap.as_float() <= 0.0 {
+                    write!(f, "min_gap must be greater than 0 yards, got {:.0}", min_gap.as_float())
+                } else {
+                    write!(
+                        f,
+                        "min ({:.0} yards) must be less than max ({:.0} yards)",
+                        min.as_float(),
+                        max.as_float()
+                    )
+                }
+            }
+        }
     }
 }
-struct RandYardsIter {
-    rng: ::rand::prelude::ThreadRng,
+
+impl std::error::Error for SpacingError {}
+
+/// Samples `count` yardages from `bounds` such that every pair of samples is
+/// at least `min_gap` apart, enforced against *all* previously accepted
+/// samples rather than only the most recently drawn one. Each step draws
+/// several candidates and keeps the one with the largest nearest-neighbor
+/// distance to the existing set (Mitchell's best-candidate method), which
+/// spreads stations evenly rather than letting them cluster.
+fn sample_spaced_yards(
     bounds: Range<Yards>,
     min_gap: Yards,
-    last: Option<Yards>,
-}
-impl Iterator for RandYardsIter {
-    type Item = Yards;
-    fn next(&mut self) -> Option<Self::Item> {
-        use ::rand::prelude::Rng;
-        loop {
-            let y = self.rng.gen_range(self.bounds.clone());
-            match self.last {
-                Some(ly) if ly.abs_diff(y) < self.min_gap => continue,
-                _ => {
-                    self.last = Some(y);
-                    return Some(y);
+    count: usize,
+    rng: &mut ChaCha8Rng,
+) -> Result<Vec<Yards>, SpacingError> {
+    if bounds.start >= bounds.end || min_gap.as_float() <= 0.0 {
+        return Err(SpacingError::InvalidRange {
+            min: bounds.start,
+            max: bounds.end,
+            min_gap,
+        });
+    }
+    let range = bounds.start.abs_diff(bounds.end);
+    if min_gap.as_float() * count as f64 > range.as_float() {
+        return Err(SpacingError::InfeasibleRange {
+            min_gap,
+            count,
+            range,
+        });
+    }
+
+    let mut accepted: Vec<Yards> = Vec::with_capacity(count);
+    while accepted.len() < count {
+        let k = BEST_CANDIDATE_BASE_K + accepted.len() / 4;
+        let mut best: Option<(Yards, Yards)> = None;
+        let mut attempts = 0;
+        while best.is_none() && attempts < MAX_CANDIDATE_ATTEMPTS {
+            for _ in 0..k.min(MAX_CANDIDATE_ATTEMPTS - attempts) {
+                attempts += 1;
+                let candidate = rng.gen_range(bounds.clone());
+                let idx = accepted.partition_point(|&y| y < candidate);
+                let far_from_lower = idx
+                    .checked_sub(1)
+                    .map_or(true, |i| accepted[i].abs_diff(candidate) >= min_gap);
+                let far_from_upper = accepted
+                    .get(idx)
+                    .map_or(true, |&y| y.abs_diff(candidate) >= min_gap);
+                if !(far_from_lower && far_from_upper) {
+                    continue;
+                }
+                let nearest = match (idx.checked_sub(1).map(|i| accepted[i]), accepted.get(idx)) {
+                    (Some(lo), Some(&hi)) => {
+                        lo.abs_diff(candidate).min(hi.abs_diff(candidate))
+                    }
+                    (Some(lo), None) => lo.abs_diff(candidate),
+                    (None, Some(&hi)) => hi.abs_diff(candidate),
+                    (None, None) => candidate
+                        .abs_diff(bounds.start)
+                        .min(candidate.abs_diff(bounds.end)),
+                };
+                if best.map_or(true, |(_, best_dist)| nearest > best_dist) {
+                    best = Some((candidate, nearest));
                 }
             }
         }
+        match best {
+            Some((candidate, _)) => {
+                let idx = accepted.partition_point(|&y| y < candidate);
+                accepted.insert(idx, candidate);
+            }
+            None => return Err(SpacingError::Exhausted { min_gap, count }),
+        }
+    }
+    Ok(accepted)
+}
+
+/// Upper bound on proposed station sets before giving up on reaching a
+/// requested average target distance.
+const MAX_TARGET_AVG_ATTEMPTS: usize = 200;
+
+fn mean_yards(samples: &[Yards]) -> Yards {
+    let sum: f64 = samples.iter().map(|y| y.as_float()).sum();
+    Yards::from_float(sum / samples.len() as f64)
+}
+
+/// Translates every sample by as much of `desired_offset` as fits within
+/// `bounds`, clamping so the lowest/highest sample never leaves the range.
+/// Shifting the whole set rather than individual stations leaves every
+/// pairwise gap unchanged, so `min_gap` stays satisfied automatically, and it
+/// can move the mean by the full width of whatever headroom is left between
+/// the set and the edges of `bounds` instead of a fraction of a yard.
+fn shift_sample_set(samples: &mut [Yards], bounds: &Range<Yards>, desired_offset: f64) {
+    let (Some(&min_sample), Some(&max_sample)) = (samples.first(), samples.last()) else {
+        return;
+    };
+    let max_shift_up = bounds.end.as_float() - max_sample.as_float();
+    let max_shift_down = bounds.start.as_float() - min_sample.as_float();
+    let offset = desired_offset.clamp(max_shift_down, max_shift_up);
+    for y in samples.iter_mut() {
+        *y = Yards::from_float(y.as_float() + offset);
+    }
+}
+
+/// Proposes spaced yardage sets (via [`sample_spaced_yards`]) and shifts each
+/// set as a whole toward `target_avg` until its mean target distance lands
+/// within `tolerance`, accepting the first such set found.
+fn sample_with_target_avg(
+    bounds: Range<Yards>,
+    min_gap: Yards,
+    count: usize,
+    target_avg: Yards,
+    tolerance: Yards,
+    rng: &mut ChaCha8Rng,
+) -> Result<Vec<Yards>, SpacingError> {
+    for _ in 0..MAX_TARGET_AVG_ATTEMPTS {
+        let mut sample = sample_spaced_yards(bounds.clone(), min_gap, count, rng)?;
+        let desired_offset = target_avg.as_float() - mean_yards(&sample).as_float();
+        shift_sample_set(&mut sample, &bounds, desired_offset);
+        if mean_yards(&sample).abs_diff(target_avg) <= tolerance {
+            return Ok(sample);
+        }
     }
+    Err(SpacingError::TargetAvgUnreachable {
+        target_avg,
+        tolerance,
+    })
 }
 
 const NUM_STATIONS: usize = 20;
@@ -81,9 +253,26 @@ fn new_random_challenge(
     inner_score: usize,
     mid_score: usize,
     outer_score: usize,
-) -> FSXChallenge {
-    let stations: Vec<_> = yards_within(dist.clone(), min_gap)
-        .take(NUM_STATIONS)
+    seed: Option<u64>,
+    target_avg: Option<Yards>,
+    target_avg_tolerance: Yards,
+) -> Result<FSXChallenge, SpacingError> {
+    let seed = seed.unwrap_or_else(|| ::rand::thread_rng().gen());
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let yards = match target_avg {
+        Some(target_avg) => sample_with_target_avg(
+            dist.clone(),
+            min_gap,
+            NUM_STATIONS,
+            target_avg,
+            target_avg_tolerance,
+            &mut rng,
+        )?,
+        None => sample_spaced_yards(dist.clone(), min_gap, NUM_STATIONS, &mut rng)?,
+    };
+    let realized_avg = mean_yards(&yards);
+    let stations: Vec<_> = yards
+        .into_iter()
         .enumerate()
         .map(|(idx, yds)| Station {
             array_index: idx,
@@ -119,21 +308,27 @@ fn new_random_challenge(
     };
     let min = dist.start.as_float();
     let max = dist.end.as_float();
-    let name = format!(r"{min:.0} - {max:.0} {uid:08x}");
+    let name = match target_avg {
+        Some(_) => format!(
+            r"{min:.0} - {max:.0} {uid:08x} seed={seed} avg={:.0}",
+            realized_avg.as_float()
+        ),
+        None => format!(r"{min:.0} - {max:.0} {uid:08x} seed={seed}"),
+    };
 
-    FSXChallenge {
+    Ok(FSXChallenge {
         name,
         num_stations: NUM_STATIONS,
         stations,
-    }
+    })
 }
 
 use axum::{
     extract::Query,
     http::StatusCode,
     response::{Html, IntoResponse},
-    routing::get,
-    Router,
+    routing::{get, post},
+    Json, Router,
 };
 use std::net::SocketAddr;
 
@@ -141,7 +336,9 @@ use std::net::SocketAddr;
 async fn main() {
     tracing_subscriber::fmt::init();
 
-    let app = Router::new().route("/", get(rand_challenge));
+    let app = Router::new()
+        .route("/", get(rand_challenge))
+        .route("/import", post(import_challenge));
 
     let addr = SocketAddr::from(([0, 0, 0, 0], 3000));
     tracing::debug!("listening on {}", addr);
@@ -151,6 +348,20 @@ async fn main() {
         .unwrap();
 }
 
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum OutputFormat {
+    Xml,
+    Json,
+    Csv,
+}
+
+impl Default for OutputFormat {
+    fn default() -> Self {
+        OutputFormat::Xml
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct ChallengeInput {
     min: Yards,
@@ -162,7 +373,42 @@ struct ChallengeInput {
     inner_score: Option<usize>,
     mid_score: Option<usize>,
     outer_score: Option<usize>,
+    format: Option<OutputFormat>,
+    seed: Option<u64>,
+    target_avg: Option<Yards>,
+    target_avg_tolerance: Option<Yards>,
+}
+
+fn stations_to_csv(stations: &[Station]) -> String {
+    let mut csv = String::from(
+        "array_index,desc,station_num,trgt_dist_yds_am,trgt_dist_yds_pro,trgt_dist_yds_women,\
+inner_ring_diam_yds_am,mid_ring_diam_yds_am,outer_ring_diam_yds_am,\
+inner_ring_diam_yds_pro,mid_ring_diam_yds_pro,outer_ring_diam_yds_pro,\
+inner_score,mid_score,outer_score\n",
+    );
+    for station in stations {
+        csv.push_str(&format!(
+            "{},{},{},{:.1},{:.1},{:.1},{:.1},{:.1},{:.1},{:.1},{:.1},{:.1},{},{},{}\n",
+            station.array_index,
+            station.desc,
+            station.station_num,
+            station.trgt_dist_am.to_yards().as_float(),
+            station.trgt_dist_pro.to_yards().as_float(),
+            station.trgt_dist_women.to_yards().as_float(),
+            station.inner_ring_diam_am.to_yards().as_float(),
+            station.mid_ring_diam_am.to_yards().as_float(),
+            station.outer_ring_diam_am.to_yards().as_float(),
+            station.inner_ring_diam_pro.to_yards().as_float(),
+            station.mid_ring_diam_pro.to_yards().as_float(),
+            station.outer_ring_diam_pro.to_yards().as_float(),
+            station.inner_score,
+            station.mid_score,
+            station.outer_score,
+        ));
+    }
+    csv
 }
+
 // basic handler that responds with a static string
 async fn rand_challenge(input: Option<Query<ChallengeInput>>) -> impl IntoResponse {
     match input {
@@ -176,8 +422,12 @@ async fn rand_challenge(input: Option<Query<ChallengeInput>>) -> impl IntoRespon
             inner_score,
             mid_score,
             outer_score,
+            format,
+            seed,
+            target_avg,
+            target_avg_tolerance,
         })) => {
-            let challenge = new_random_challenge(
+            let challenge = match new_random_challenge(
                 min..max,
                 min_gap.unwrap_or_else(|| Yards::new(10)),
                 inner_ring.unwrap_or_else(|| Yards::new(8)),
@@ -186,22 +436,40 @@ async fn rand_challenge(input: Option<Query<ChallengeInput>>) -> impl IntoRespon
                 inner_score.unwrap_or(5),
                 mid_score.unwrap_or(3),
                 outer_score.unwrap_or(1),
-            );
-            let filename = format!("{}.xml", challenge.name);
-            match quick_xml::se::to_string(&challenge) {
-                Ok(challenge) => (
+                seed,
+                target_avg,
+                target_avg_tolerance.unwrap_or_else(|| Yards::new(2)),
+            ) {
+                Ok(challenge) => challenge,
+                Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+            };
+            let format = format.unwrap_or_default();
+            let body = match format {
+                OutputFormat::Xml => quick_xml::se::to_string(&challenge)
+                    .map_err(|e| format!("Error: {}", e)),
+                OutputFormat::Json => {
+                    serde_json::to_string(&challenge).map_err(|e| format!("Error: {}", e))
+                }
+                OutputFormat::Csv => Ok(stations_to_csv(&challenge.stations)),
+            };
+            let (content_type, ext) = match format {
+                OutputFormat::Xml => ("application/xml", "xml"),
+                OutputFormat::Json => ("application/json", "json"),
+                OutputFormat::Csv => ("text/csv", "csv"),
+            };
+            let filename = format!("{}.{}", challenge.name, ext);
+            match body {
+                Ok(body) => (
                     StatusCode::OK,
-                    [("Content-Type", "application/xml")],
+                    [("Content-Type", content_type)],
                     [(
                         "Content-Disposition",
                         format!(r#"inline; filename="{filename}""#),
                     )],
-                    challenge,
+                    body,
                 )
                     .into_response(),
-                Err(e) => {
-                    (StatusCode::INTERNAL_SERVER_ERROR, format!("Error: {}", e)).into_response()
-                }
+                Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
             }
         }
         None => INPUT_FORM.into_response(),
@@ -251,9 +519,147 @@ const INPUT_FORM: Html<&str> = Html(
                       Outer Score:
                       <input value=1 type="text" name="outer_score">
                   </label>
+                  <label for="format">
+                      Format:
+                      <select name="format">
+                          <option value="xml">XML</option>
+                          <option value="json">JSON</option>
+                          <option value="csv">CSV</option>
+                      </select>
+                  </label>
+                  <label for="seed">
+                      Seed (for a repeatable challenge):
+                      <input type="text" name="seed">
+                  </label>
+                  <label for="target_avg">
+                      Target Average Yardage (difficulty budget):
+                      <input type="text" name="target_avg">
+                  </label>
+                  <label for="target_avg_tolerance">
+                      Target Average Tolerance:
+                      <input value=2 type="text" name="target_avg_tolerance">
+                  </label>
                   <input type="submit">
               </form>
           </body>
       </html>
     "#,
 );
+
+#[derive(Debug, Serialize)]
+struct ValidationReport {
+    valid: bool,
+    errors: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ImportInput {
+    min_gap: Option<Yards>,
+}
+
+fn validate_challenge(challenge: &FSXChallenge, min_gap: Yards) -> ValidationReport {
+    let mut errors = Vec::new();
+
+    if challenge.num_stations != challenge.stations.len() {
+        errors.push(format!(
+            "num_stations ({}) does not match stations.len() ({})",
+            challenge.num_stations,
+            challenge.stations.len()
+        ));
+    }
+
+    for (idx, station) in challenge.stations.iter().enumerate() {
+        if station.array_index != idx {
+            errors.push(format!(
+                "station at position {idx} has non-consecutive array_index {}",
+                station.array_index
+            ));
+        }
+        if station.station_num != idx + 1 {
+            errors.push(format!(
+                "station at position {idx} has non-consecutive station_num {}",
+                station.station_num
+            ));
+        }
+        if !(station.inner_ring_diam_am.to_yards() < station.mid_ring_diam_am.to_yards()
+            && station.mid_ring_diam_am.to_yards() < station.outer_ring_diam_am.to_yards())
+        {
+            errors.push(format!(
+                "station {} amateur ring diameters are not strictly increasing",
+                station.station_num
+            ));
+        }
+        if !(station.inner_ring_diam_pro.to_yards() < station.mid_ring_diam_pro.to_yards()
+            && station.mid_ring_diam_pro.to_yards() < station.outer_ring_diam_pro.to_yards())
+        {
+            errors.push(format!(
+                "station {} pro ring diameters are not strictly increasing",
+                station.station_num
+            ));
+        }
+    }
+
+    let mut yards: Vec<_> = challenge
+        .stations
+        .iter()
+        .map(|s| s.trgt_dist_am.to_yards())
+        .collect();
+    yards.sort();
+    for pair in yards.windows(2) {
+        if pair[0].abs_diff(pair[1]) < min_gap {
+            errors.push(format!(
+                "stations at {:.0} and {:.0} yards violate the minimum gap of {:.0} yards",
+                pair[0].as_float(),
+                pair[1].as_float(),
+                min_gap.as_float()
+            ));
+        }
+    }
+
+    ValidationReport {
+        valid: errors.is_empty(),
+        errors,
+    }
+}
+
+async fn import_challenge(
+    input: Option<Query<ImportInput>>,
+    body: String,
+) -> impl IntoResponse {
+    let min_gap = input
+        .and_then(|Query(ImportInput { min_gap })| min_gap)
+        .unwrap_or_else(|| Yards::new(10));
+    match quick_xml::de::from_str::<FSXChallenge>(&body) {
+        Ok(challenge) => Json(validate_challenge(&challenge, min_gap)).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, format!("Error: {}", e)).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn xml_round_trip_preserves_repeated_station_elements() {
+        let challenge = new_random_challenge(
+            Yards::new(100)..Yards::new(300),
+            Yards::new(5),
+            Yards::new(8),
+            Yards::new(16),
+            Yards::new(24),
+            5,
+            3,
+            1,
+            Some(42),
+            None,
+            Yards::new(2),
+        )
+        .expect("spacing is feasible for this range");
+
+        let xml = quick_xml::se::to_string(&challenge).expect("challenge serializes to xml");
+        let round_tripped: FSXChallenge =
+            quick_xml::de::from_str(&xml).expect("serialized xml deserializes back");
+
+        assert_eq!(challenge, round_tripped);
+    }
+}
@@ -2,7 +2,7 @@ use serde::{Deserialize, Serialize};
 
 const YARDS_PER_METER: f64 = 1.09361;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Meters(usize);
 impl Meters {
     pub fn from_float(x: f64) -> Self {